@@ -1,11 +1,14 @@
 use futures::Future;
+use rand::Rng;
 use reqwest::{StatusCode};
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::time::{Instant, Duration};
 use std::{thread};
 
 /// Structure describe single test round.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Test {
     /// Test description
     pub label: String,
@@ -19,6 +22,142 @@ pub struct Test {
     pub repeats: usize,
     /// Delay between repeats, defined in seconds
     pub delay_s: u64,
+    /// Optional offered-load cap, in requests per second. `None` fires as fast
+    /// as the buffer allows; `Some(rate)` paces requests through a GCRA limiter.
+    #[serde(default)]
+    pub requests_per_second: Option<f64>,
+    /// Maximum number of retries for a transient failure before giving up
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Base of the exponential backoff, in milliseconds
+    #[serde(default = "default_backoff_base_ms")]
+    pub backoff_base_ms: u64,
+    /// Upper bound of the backoff delay, in milliseconds
+    #[serde(default = "default_backoff_cap_ms")]
+    pub backoff_cap_ms: u64,
+    /// Transport to benchmark; defaults to a plain HTTP GET
+    #[serde(default)]
+    pub protocol: Protocol,
+}
+
+fn default_backoff_base_ms() -> u64 { 50 }
+fn default_backoff_cap_ms() -> u64 { 5_000 }
+
+/// Transport benchmarked by a single [`Test`].
+///
+/// `HttpGet` fetches `url_get` over HTTP; `WebSocket` opens a connection to
+/// `url_get`, sends `send_payload`, and times the round-trip to the first
+/// response frame — an RPC-over-WebSocket style request/response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Protocol {
+    HttpGet,
+    WebSocket { send_payload: String },
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Protocol::HttpGet
+    }
+}
+
+/// Outcome of a single request, carrying the timing and how many attempts it
+/// took so the summary can report retry rates on flaky endpoints.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestResult {
+    /// Final status — the status of the last attempt, or
+    /// [`StatusCode::SERVICE_UNAVAILABLE`] when a connection error exhausted
+    /// all retries.
+    pub status: StatusCode,
+    /// Wall-clock latency from the first attempt to the final outcome,
+    /// including any backoff sleeps between retries, in seconds
+    pub duration: f32,
+    /// Number of attempts made, i.e. `1 + retries`
+    pub attempts: u32,
+}
+
+/// Exponential-backoff-with-full-jitter retry policy for transient failures.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Maximum retries after the initial attempt
+    pub max_retries: u32,
+    base: u64,
+    cap: u64,
+}
+
+impl RetryPolicy {
+    /// Builds a policy from a [`Test`]'s backoff configuration.
+    pub fn new(max_retries: u32, backoff_base_ms: u64, backoff_cap_ms: u64) -> Self {
+        RetryPolicy { max_retries, base: backoff_base_ms, cap: backoff_cap_ms }
+    }
+
+    /// Whether a request that returned `status` is worth retrying.
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+    }
+
+    /// Full-jitter backoff before `attempt` (0-based): a uniform random delay in
+    /// `0..=min(cap, base * 2^attempt)` milliseconds.
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exp = self.base.saturating_mul(1u64 << attempt.min(32));
+        let ceiling = exp.min(self.cap);
+        let jittered = rand::thread_rng().gen_range(0..=ceiling);
+        Duration::from_millis(jittered)
+    }
+}
+
+/// Client-side rate limiter implementing the Generic Cell Rate Algorithm.
+///
+/// Shared across all concurrent requests of a single test so the harness
+/// offers a *controlled* load rather than firing everything at once. The
+/// limiter keeps a theoretical arrival time (TAT); each [`acquire`] sleeps
+/// until the next emission slot, allowing a small burst of `B` requests.
+///
+/// [`acquire`]: GcraLimiter::acquire
+pub struct GcraLimiter {
+    tat: Arc<Mutex<Instant>>,
+    /// Emission interval `T = 1 / rate`
+    interval: Duration,
+    /// Burst allowance `B * T`, as a tolerance subtracted from the TAT
+    burst_allowance: Duration,
+}
+
+impl GcraLimiter {
+    /// Builds a limiter for `rate` requests per second with a burst of `burst`
+    /// requests. The TAT starts at construction time so the first request
+    /// passes without delay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not a positive, finite number. Callers reading
+    /// `rate` from config (e.g. [`Test::requests_per_second`]) should
+    /// validate it themselves and fail gracefully before reaching here.
+    pub fn new(rate: f64, burst: f64) -> Self {
+        assert!(rate > 0.0 && rate.is_finite(), "GcraLimiter rate must be a positive, finite requests/second, got {}", rate);
+        let interval = Duration::from_secs_f64(1.0 / rate);
+        GcraLimiter {
+            tat: Arc::new(Mutex::new(Instant::now())),
+            interval,
+            burst_allowance: interval.mul_f64(burst),
+        }
+    }
+
+    /// Blocks (asynchronously) until the next request is allowed to be sent,
+    /// then advances the TAT by one emission interval.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut tat = self.tat.lock().unwrap();
+            let now = Instant::now();
+            let allowed_at = tat.checked_sub(self.burst_allowance).unwrap_or(now);
+            let wait = allowed_at.checked_duration_since(now).unwrap_or(Duration::ZERO);
+            let base = if now > *tat { now } else { *tat };
+            *tat = base + self.interval;
+            wait
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
 }
 
 impl std::fmt::Display for Test {
@@ -28,19 +167,70 @@ impl std::fmt::Display for Test {
     }
 }
 
+/// Latency distribution of a single test, in seconds.
+///
+/// Produced from every individual request's duration (across all repeats),
+/// so it exposes tail behavior the bare mean/std-dev pair used to hide.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Statistics {
+    /// Arithmetic mean of all per-request latencies
+    pub mean: f32,
+    /// Population standard deviation of the latencies
+    pub std_dev: f32,
+    /// Fastest request observed
+    pub min: f32,
+    /// Slowest request observed
+    pub max: f32,
+    /// Median latency (50th percentile)
+    pub p50: f32,
+    /// 95th percentile latency
+    pub p95: f32,
+    /// 99th percentile latency
+    pub p99: f32,
+    /// Largest gap between two consecutive sorted samples — a spread/stability indicator
+    pub maximum_jump: f32,
+}
+
+/// A backend that issues the requests of a single repeat and reports each
+/// request's outcome. Implemented by the async futures-stream loader and, behind
+/// the `blocking` feature, by a thread-pool loader, so both share the timing,
+/// statistics and plotting pipeline in [`average_time`].
+pub trait Loader {
+    /// Backend label shown in the summary title, e.g. `"async ordered"` or `"blocking"`.
+    fn name(&self) -> &'static str;
+
+    /// Runs `urls` with up to `concurrent` in flight, pacing through `limiter`
+    /// when present and retrying transient failures per `retry`.
+    fn execute<'a>(
+        &'a self,
+        concurrent: usize,
+        urls: Vec<String>,
+        limiter: Option<Arc<GcraLimiter>>,
+        retry: RetryPolicy,
+        protocol: Protocol,
+    ) -> Pin<Box<dyn Future<Output = Vec<RequestResult>> + Send + 'a>>;
+}
+
+/// Result of running one [`Test`]: its latency distribution plus the raw
+/// per-request timings that produced it, kept so a [`BenchmarkReport`] can be
+/// diffed across runs.
+pub struct TestOutcome {
+    /// Computed latency distribution
+    pub stats: Statistics,
+    /// Every individual request latency gathered across all repeats, in seconds
+    pub latencies: Vec<f32>,
+}
+
 /// A function to proceed series of test.
-/// Returns the vector of tuples represents the (mean, standard deviation) execution time, definded in seconds
-pub async fn average_time<F, Fut>(tests: &Vec<Test>, async_func: F, title: &str) -> Vec<(f32,f32)>
-where
-    F: Fn(usize,Vec<String>) -> Fut,
-    Fut: Future<Output = Vec<(StatusCode, String)>>
-{
-    println!("\n🌊🌊🌊 {} 🌊🌊🌊", title);
+/// Returns one [`TestOutcome`] per test, holding the per-request latency
+/// distribution and the raw timings, defined in seconds.
+pub async fn average_time<L: Loader>(tests: &Vec<Test>, loader: &L, title: &str) -> Vec<TestOutcome> {
+    println!("\n🌊🌊🌊 {} [{}] 🌊🌊🌊", title, loader.name());
     let mut ret = Vec::new();
     for (test_idx, test) in tests.iter().enumerate() {
         println!("\n🚀Test{} - {}", test_idx+1, test);
 
-        let mut results = Vec::new();
+        let mut latencies = Vec::new();
         for idx in 1..=test.repeats {
             if idx != 1 && test_idx != 0 {
                 thread::sleep(Duration::from_secs(test.delay_s));
@@ -48,35 +238,48 @@ where
 
             let urls: Vec<String> = vec![test.url_get.clone(); test.requests_number];
 
+            let limiter = test.requests_per_second
+                .map(|rate| Arc::new(GcraLimiter::new(rate, 1.0)));
+            let retry = RetryPolicy::new(test.max_retries, test.backoff_base_ms, test.backoff_cap_ms);
+
             let now = Instant::now();
-            let resp = async_func(test.concurrent_requests, urls).await;
+            let resp = loader.execute(test.concurrent_requests, urls, limiter, retry, test.protocol.clone()).await;
             let time = now.elapsed().as_secs_f32();
 
-            resp.iter()
-                .for_each(|r|
-                    if !r.0.is_success() {
-                        let err = format!("{}", r.0);
-                        println!("Error: {}", err);
-                        std::process::exit(1);
-                    }
-                );
-
-            println!("  [{}/{}] time: {}s", idx, test.repeats, time);
-            results.push(time);
+            let failures = resp.iter().filter(|r| !r.status.is_success()).count();
+            let retried = resp.iter().filter(|r| r.attempts > 1).count();
+            if failures != 0 || retried != 0 {
+                println!("  [{}/{}] time: {}s ({} failed, {} retried)",
+                    idx, test.repeats, time, failures, retried);
+            } else {
+                println!("  [{}/{}] time: {}s", idx, test.repeats, time);
+            }
+
+            latencies.extend(resp.into_iter().map(|r| r.duration));
         }
 
-        let (mean, std_dev) = statistic(&results)
+        let stats = statistic(&latencies)
             .expect("Repeats equal zero");
 
-        println!("SUMMARY: {}±{}s", mean, std_dev);
+        println!("SUMMARY: mean {}±{}s, min {}s, max {}s, p50 {}s, p95 {}s, p99 {}s, max jump {}s",
+            stats.mean, stats.std_dev, stats.min, stats.max, stats.p50, stats.p95, stats.p99, stats.maximum_jump);
 
-        ret.push((mean, std_dev));
+        ret.push(TestOutcome { stats, latencies });
     }
 
     ret
 }
 
-fn statistic(data: &[f32]) -> Option<(f32,f32)> {
+/// Returns the `p`-th percentile of an already ascending-sorted slice using the
+/// nearest-rank method: index `ceil((p/100) * n) - 1` clamped to `0..n`.
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let n = sorted.len();
+    let idx = ((p / 100.0) * (n as f32)).ceil() as usize;
+    let idx = idx.saturating_sub(1).min(n - 1);
+    sorted[idx]
+}
+
+fn statistic(data: &[f32]) -> Option<Statistics> {
     if data.len() == 0 {
         return None;
     }
@@ -91,9 +294,131 @@ fn statistic(data: &[f32]) -> Option<(f32,f32)> {
         })
         .sum::<f32>() / count;
 
-    let std_deviation = variance.sqrt();
+    let std_dev = variance.sqrt();
+
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
-    Some((mean, std_deviation))
+    let maximum_jump = sorted.windows(2)
+        .map(|w| w[1] - w[0])
+        .fold(0.0_f32, f32::max);
+
+    Some(Statistics {
+        mean,
+        std_dev,
+        min: sorted[0],
+        max: sorted[sorted.len() - 1],
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+        maximum_jump,
+    })
+}
+
+/// Environment stamp recorded with every report so results are attributable
+/// and comparable across machines and runs.
+#[derive(Serialize, Deserialize)]
+pub struct Environment {
+    /// Wall-clock time the report was produced, as Unix epoch seconds
+    pub timestamp_unix: u64,
+    /// Host the benchmark ran on (from `$HOSTNAME`, or `"unknown"`)
+    pub host: String,
+    /// Available hardware parallelism of the host
+    pub concurrency: usize,
+}
+
+impl Environment {
+    /// Captures the current environment.
+    pub fn capture() -> Self {
+        let timestamp_unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string());
+        let concurrency = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(0);
+        Environment { timestamp_unix, host, concurrency }
+    }
+}
+
+/// Per-test slice of a [`BenchmarkReport`]: the full config that was run
+/// together with every request latency from all repeats and the computed statistics, so a
+/// regression caused by a config change (e.g. a loosened retry policy or a
+/// raised rate limit) is visible in the report, not just one caused by code.
+#[derive(Serialize, Deserialize)]
+pub struct TestReport {
+    pub config: Test,
+    /// Every individual request latency, in seconds
+    pub timings: Vec<f32>,
+    pub statistics: Statistics,
+}
+
+/// Machine-readable report for one `average_time` run, suitable for diffing
+/// across runs to gate latency regressions in CI.
+#[derive(Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub title: String,
+    pub environment: Environment,
+    pub tests: Vec<TestReport>,
+}
+
+impl BenchmarkReport {
+    /// Builds a report from the tests of a run and their outcomes.
+    pub fn new(title: &str, tests: &[Test], outcomes: &[TestOutcome]) -> Self {
+        let tests = tests.iter().zip(outcomes.iter())
+            .map(|(test, outcome)| TestReport {
+                config: test.clone(),
+                timings: outcome.latencies.clone(),
+                statistics: outcome.stats,
+            })
+            .collect();
+
+        BenchmarkReport {
+            title: title.to_string(),
+            environment: Environment::capture(),
+            tests,
+        }
+    }
+
+    /// Writes the given reports to `path` as a single JSON document.
+    pub fn write_json(path: &str, reports: &[BenchmarkReport]) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, reports)?;
+        Ok(())
+    }
+
+    /// Writes the given reports to `path` as a flat CSV, one row per test.
+    pub fn write_csv(path: &str, reports: &[BenchmarkReport]) -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write;
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "label,concurrent_requests,requests_number,mean,std_dev,p95,requests_per_second,max_retries")?;
+        for report in reports {
+            for test in &report.tests {
+                writeln!(file, "{},{},{},{},{},{},{},{}",
+                    csv_quote(&test.config.label),
+                    test.config.concurrent_requests,
+                    test.config.requests_number,
+                    test.statistics.mean,
+                    test.statistics.std_dev,
+                    test.statistics.p95,
+                    test.config.requests_per_second.map(|r| r.to_string()).unwrap_or_default(),
+                    test.config.max_retries)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quotes `field` for a CSV row per RFC 4180 if it contains a comma, quote,
+/// or newline, doubling any embedded quotes. Free-form config strings like
+/// [`Test::label`] are otherwise able to shift every column after them.
+fn csv_quote(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
 }
 
 #[cfg(test)]
@@ -102,10 +427,12 @@ mod tests {
     #[test]
     fn stats_basic() {
         let data = [10.0, 12.0, 23.0, 23.0, 16.0, 23.0, 21.0, 16.0];
-        let (mean, std_dev) = statistic(&data).unwrap();
+        let stats = statistic(&data).unwrap();
 
-        assert_eq!(18.0, mean);
-        assert_eq!(4.8989797, std_dev);
+        assert_eq!(18.0, stats.mean);
+        assert_eq!(4.8989797, stats.std_dev);
+        assert_eq!(10.0, stats.min);
+        assert_eq!(23.0, stats.max);
     }
 
     #[test]
@@ -113,5 +440,59 @@ mod tests {
         let data = [];
         assert!(statistic(&data).is_none());
     }
+
+    #[test]
+    fn percentiles_nearest_rank() {
+        let sorted: Vec<f32> = (1..=10).map(|v| v as f32).collect();
+        assert_eq!(5.0, percentile(&sorted, 50.0));
+        assert_eq!(10.0, percentile(&sorted, 95.0));
+        assert_eq!(10.0, percentile(&sorted, 99.0));
+        assert_eq!(1.0, percentile(&sorted, 0.0));
+    }
+
+    #[test]
+    fn maximum_jump_is_largest_consecutive_gap() {
+        let data = [1.0, 2.0, 10.0, 11.0];
+        let stats = statistic(&data).unwrap();
+        assert_eq!(8.0, stats.maximum_jump);
+    }
+
+    #[test]
+    fn is_retryable_status_covers_transient_and_permanent_codes() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(RetryPolicy::is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 400, 401, 403, 404] {
+            assert!(!RetryPolicy::is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
+    #[test]
+    fn backoff_delay_never_exceeds_cap() {
+        let retry = RetryPolicy::new(10, 50, 5_000);
+        for attempt in [0, 1, 5, 10, 32, 1_000] {
+            assert!(retry.backoff_delay(attempt) <= Duration::from_millis(5_000));
+        }
+    }
+
+    #[test]
+    fn csv_quote_passes_through_plain_field() {
+        assert_eq!("staging", csv_quote("staging"));
+    }
+
+    #[test]
+    fn csv_quote_escapes_comma_and_embedded_quotes() {
+        assert_eq!("\"staging, us-east\"", csv_quote("staging, us-east"));
+        assert_eq!("\"say \"\"hi\"\"\"", csv_quote("say \"hi\""));
+    }
+
+    #[tokio::test]
+    async fn gcra_limiter_spaces_requests_by_interval() {
+        let limiter = GcraLimiter::new(100.0, 0.0);
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(9));
+    }
 }
 
@@ -1,8 +1,17 @@
-use futures::{stream, StreamExt};
-use reqwest::{Client, StatusCode};
+use futures::Future;
+#[cfg(not(feature = "blocking"))]
+use futures::{stream, SinkExt, StreamExt};
+use reqwest::StatusCode;
+#[cfg(not(feature = "blocking"))]
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs::File;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use benchmark::{BenchmarkReport, GcraLimiter, Loader, Protocol, RequestResult, RetryPolicy};
 use textplots::{Chart, Plot, Shape};
 
 #[derive(Serialize, Deserialize)]
@@ -10,36 +19,319 @@ pub struct Config {
     pub tests: Vec<benchmark::Test>,
 }
 
-async fn ordered_get(buffer_size: usize, urls: Vec<String>) -> Vec<(StatusCode, String)> {
-    let client = Client::new();
+/// Sends a single GET, retrying transient failures (connection errors and
+/// retryable statuses) with exponential backoff and full jitter. Instead of
+/// aborting the process on failure, the exhausted outcome is returned so the
+/// statistics can report retry and failure rates.
+#[cfg(not(feature = "blocking"))]
+async fn send_with_retry(client: &Client, url: String, retry: RetryPolicy) -> RequestResult {
+    let start = Instant::now();
+    let mut attempt = 0;
+    loop {
+        let result = client.get(&url).send().await;
 
-    stream::iter(urls)
-        .map(|url| {
-            let client = &client;
-            async move {
-                let resp = client.get(url).send().await.expect("No connection to the server.");
-                (resp.status(), resp.text().await.expect("No response from server."))
+        let status = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                match resp.text().await {
+                    Ok(_) if status.is_success() || !RetryPolicy::is_retryable_status(status) => {
+                        return RequestResult { status, duration: start.elapsed().as_secs_f32(), attempts: attempt + 1 };
+                    }
+                    Ok(_) => Some(status),
+                    Err(_) => None,
+                }
+            }
+            Err(_) => None,
+        };
+
+        if attempt >= retry.max_retries {
+            return RequestResult {
+                status: status.unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                duration: start.elapsed().as_secs_f32(),
+                attempts: attempt + 1,
+            };
+        }
+
+        tokio::time::sleep(retry.backoff_delay(attempt)).await;
+        attempt += 1;
+    }
+}
+
+/// Async futures-stream loader — the default backend. When `ordered` is set it
+/// yields results in request order via `buffered`, otherwise in completion
+/// order via `buffer_unordered`. WebSocket tests always run through
+/// `buffer_unordered`, whatever `ordered` is.
+#[cfg(not(feature = "blocking"))]
+struct AsyncLoader {
+    ordered: bool,
+}
+
+#[cfg(not(feature = "blocking"))]
+impl Loader for AsyncLoader {
+    fn name(&self) -> &'static str {
+        if self.ordered { "async ordered" } else { "async unordered" }
+    }
+
+    fn execute<'a>(
+        &'a self,
+        buffer_size: usize,
+        urls: Vec<String>,
+        limiter: Option<Arc<GcraLimiter>>,
+        retry: RetryPolicy,
+        protocol: Protocol,
+    ) -> Pin<Box<dyn Future<Output = Vec<RequestResult>> + Send + 'a>> {
+        let ordered = self.ordered;
+        Box::pin(async move {
+            if let Protocol::WebSocket { send_payload } = protocol {
+                return websocket_roundtrips(buffer_size, urls, limiter, send_payload).await;
+            }
+
+            let client = Client::new();
+
+            let requests = stream::iter(urls)
+                .map(|url| {
+                    let client = &client;
+                    let limiter = limiter.clone();
+                    async move {
+                        if let Some(limiter) = &limiter {
+                            limiter.acquire().await;
+                        }
+                        send_with_retry(client, url, retry).await
+                    }
+                });
+
+            if ordered {
+                requests.buffered(buffer_size).collect::<Vec<_>>().await
+            } else {
+                requests.buffer_unordered(buffer_size).collect::<Vec<_>>().await
             }
         })
-        .buffered(buffer_size)
-        .collect::<Vec<_>>().await
+    }
 }
 
-async fn unordered_get(buffer_size: usize, urls: Vec<String>) -> Vec<(StatusCode, String)> {
-    let client = Client::new();
+/// Opens up to `buffer_size` concurrent WebSocket connections, sends
+/// `send_payload` on each, and records the round-trip time to the first
+/// response frame as one logical request. Connection or protocol errors are
+/// reported as [`StatusCode::SERVICE_UNAVAILABLE`] so they flow through the
+/// same statistics as failed HTTP requests. Each round-trip is attempted once;
+/// failures are not retried.
+#[cfg(not(feature = "blocking"))]
+async fn websocket_roundtrips(
+    buffer_size: usize,
+    urls: Vec<String>,
+    limiter: Option<Arc<GcraLimiter>>,
+    send_payload: String,
+) -> Vec<RequestResult> {
+    use tokio_tungstenite::tungstenite::Message;
 
     stream::iter(urls)
         .map(|url| {
-            let client = &client;
+            let limiter = limiter.clone();
+            let payload = send_payload.clone();
             async move {
-                let resp = client.get(url).send().await.expect("No connection to the server.");
-                (resp.status(), resp.text().await.expect("No response from server."))
+                if let Some(limiter) = &limiter {
+                    limiter.acquire().await;
+                }
+
+                let now = Instant::now();
+                let roundtrip = async {
+                    let (mut socket, _) = tokio_tungstenite::connect_async(&url).await?;
+                    let sent = Instant::now();
+                    socket.send(Message::Text(payload)).await?;
+                    loop {
+                        match socket.next().await {
+                            Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                            Some(Ok(Message::Close(_))) => {
+                                return Err(tokio_tungstenite::tungstenite::Error::ConnectionClosed);
+                            }
+                            Some(Ok(_)) => break,
+                            Some(Err(err)) => return Err(err),
+                            None => return Err(tokio_tungstenite::tungstenite::Error::ConnectionClosed),
+                        }
+                    }
+                    Ok::<std::time::Duration, tokio_tungstenite::tungstenite::Error>(sent.elapsed())
+                };
+
+                let (status, duration) = match roundtrip.await {
+                    Ok(elapsed) => (StatusCode::OK, elapsed),
+                    Err(_) => (StatusCode::SERVICE_UNAVAILABLE, now.elapsed()),
+                };
+                RequestResult { status, duration: duration.as_secs_f32(), attempts: 1 }
             }
         })
         .buffer_unordered(buffer_size)
         .collect::<Vec<_>>().await
 }
 
+/// Classic thread-pool loader using `reqwest::blocking`. A fixed pool of
+/// `concurrent` OS threads pulls URLs off a shared queue, so the async overhead
+/// can be compared against a threaded loader on the same `Test` definitions.
+///
+/// The async GCRA limiter is not applicable to the synchronous path and is
+/// ignored here.
+#[cfg(feature = "blocking")]
+struct BlockingLoader;
+
+#[cfg(feature = "blocking")]
+impl Loader for BlockingLoader {
+    fn name(&self) -> &'static str {
+        "blocking"
+    }
+
+    fn execute<'a>(
+        &'a self,
+        concurrent: usize,
+        urls: Vec<String>,
+        limiter: Option<Arc<GcraLimiter>>,
+        retry: RetryPolicy,
+        protocol: Protocol,
+    ) -> Pin<Box<dyn Future<Output = Vec<RequestResult>> + Send + 'a>> {
+        if limiter.is_some() {
+            eprintln!("Warning: the blocking backend ignores `requests_per_second`; requests will run unpaced.");
+        }
+        if !matches!(protocol, Protocol::HttpGet) {
+            eprintln!("Warning: the blocking backend only supports HttpGet; falling back to a plain GET for url_get.");
+        }
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || blocking_pool(concurrent, urls, retry))
+                .await
+                .expect("Blocking loader panicked.")
+        })
+    }
+}
+
+/// Drives `urls` through a fixed pool of `concurrent` worker threads pulling
+/// off a shared queue, each sending synchronously with the same retry policy.
+#[cfg(feature = "blocking")]
+fn blocking_pool(concurrent: usize, urls: Vec<String>, retry: RetryPolicy) -> Vec<RequestResult> {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    let client = reqwest::blocking::Client::new();
+    let queue = Arc::new(Mutex::new(urls.into_iter().collect::<VecDeque<_>>()));
+    let results = Arc::new(Mutex::new(Vec::new()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrent.max(1) {
+            let client = &client;
+            let queue = Arc::clone(&queue);
+            let results = Arc::clone(&results);
+            scope.spawn(move || {
+                loop {
+                    let url = match queue.lock().unwrap().pop_front() {
+                        Some(url) => url,
+                        None => break,
+                    };
+                    let result = blocking_send_with_retry(client, url, retry);
+                    results.lock().unwrap().push(result);
+                }
+            });
+        }
+    });
+
+    Arc::try_unwrap(results).unwrap().into_inner().unwrap()
+}
+
+/// Synchronous counterpart of [`send_with_retry`] for the blocking backend.
+#[cfg(feature = "blocking")]
+fn blocking_send_with_retry(client: &reqwest::blocking::Client, url: String, retry: RetryPolicy) -> RequestResult {
+    let start = Instant::now();
+    let mut attempt = 0;
+    loop {
+        let result = client.get(&url).send();
+
+        let status = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                match resp.text() {
+                    Ok(_) if status.is_success() || !RetryPolicy::is_retryable_status(status) => {
+                        return RequestResult { status, duration: start.elapsed().as_secs_f32(), attempts: attempt + 1 };
+                    }
+                    Ok(_) => Some(status),
+                    Err(_) => None,
+                }
+            }
+            Err(_) => None,
+        };
+
+        if attempt >= retry.max_retries {
+            return RequestResult {
+                status: status.unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                duration: start.elapsed().as_secs_f32(),
+                attempts: attempt + 1,
+            };
+        }
+
+        std::thread::sleep(retry.backoff_delay(attempt));
+        attempt += 1;
+    }
+}
+
+/// Builds the loader for the active backend: the blocking thread-pool loader
+/// when the `blocking` feature is enabled, otherwise the async one.
+#[cfg(not(feature = "blocking"))]
+fn make_loader(ordered: bool) -> impl Loader {
+    AsyncLoader { ordered }
+}
+
+#[cfg(feature = "blocking")]
+fn make_loader(_ordered: bool) -> impl Loader {
+    BlockingLoader
+}
+
+/// Statistics fields selectable via `--metric` for the charts in `main`.
+/// `mean` is the long-standing default; the rest surface the tail behavior
+/// [`Statistics`](benchmark::Statistics) added.
+const METRICS: [&str; 6] = ["mean", "p50", "p95", "p99", "max", "maximum_jump"];
+
+/// Reads the named field off `stats`. `metric` must be one of [`METRICS`].
+fn metric_value(stats: &benchmark::Statistics, metric: &str) -> f32 {
+    match metric {
+        "mean" => stats.mean,
+        "p50" => stats.p50,
+        "p95" => stats.p95,
+        "p99" => stats.p99,
+        "max" => stats.max,
+        "maximum_jump" => stats.maximum_jump,
+        other => unreachable!("unvalidated metric {:?} reached metric_value", other),
+    }
+}
+
+/// Optional machine-readable output destinations and chart options, parsed
+/// from the CLI.
+struct OutputArgs {
+    json: Option<String>,
+    csv: Option<String>,
+    /// Which [`Statistics`](benchmark::Statistics) field to plot; one of [`METRICS`].
+    metric: String,
+}
+
+impl Default for OutputArgs {
+    fn default() -> Self {
+        OutputArgs { json: None, csv: None, metric: "mean".to_string() }
+    }
+}
+
+/// Parses `--output <path>` (JSON), `--csv <path>`, and `--metric <name>`
+/// (one of [`METRICS`], default `mean`) flags from the process arguments.
+/// Unknown arguments are ignored so existing invocations keep working.
+fn parse_output_args() -> OutputArgs {
+    let mut out = OutputArgs::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => out.json = args.next(),
+            "--csv" => out.csv = args.next(),
+            "--metric" => out.metric = args.next().graceful_exit("Missing value for --metric."),
+            _ => {}
+        }
+    }
+    if !METRICS.contains(&out.metric.as_str()) {
+        None::<()>.graceful_exit(&format!("Unknown --metric '{}'; expected one of {:?}.", out.metric, METRICS));
+    }
+    out
+}
+
 trait ErrorHandler<T> {
    fn graceful_exit(self, msg: &str) -> T;
 }
@@ -71,6 +363,21 @@ impl<T> ErrorHandler<T> for Option<T> {
     }
 }
 
+/// Rejects configs that would panic deep inside the loader, e.g. a
+/// non-positive `requests_per_second` reaching [`benchmark::GcraLimiter::new`].
+fn validate_config(config: &Config) {
+    for test in &config.tests {
+        if let Some(rate) = test.requests_per_second {
+            if !(rate > 0.0) || !rate.is_finite() {
+                None::<()>.graceful_exit(&format!(
+                    "Test '{}': requests_per_second must be a positive number (omit it or use null to disable rate limiting).",
+                    test.label,
+                ));
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let file = File::open("fixed_requests_number_250.json")
@@ -80,40 +387,57 @@ async fn main() {
     let config: Config = serde_json::from_reader(file)
         .map_err(|e| e.into())
         .graceful_exit("The configuration file is incorrect.");
+    validate_config(&config);
+
+    let output = parse_output_args();
+    let mut reports: Vec<BenchmarkReport> = Vec::new();
 
    {
-        let results = benchmark::average_time(&config.tests, ordered_get, "Ordered buffer - 250 requests").await;
+        #[cfg(not(feature = "blocking"))]
+        let title = "Ordered buffer - 250 requests";
+        #[cfg(feature = "blocking")]
+        let title = "Blocking - 250 requests";
+        let results = benchmark::average_time(&config.tests, &make_loader(true), title).await;
 
         let points: Vec<(f32,f32)> = config.tests.iter()
             .map(|t| t.concurrent_requests as f32)
             .zip(results.iter()
-                .map(|r| r.0))
+                .map(|r| metric_value(&r.stats, &output.metric)))
             .collect();
 
         let max = config.tests.iter().map(|t| t.concurrent_requests).max().unwrap() as f32;
         let min = config.tests.iter().map(|t| t.concurrent_requests).min().unwrap() as f32;
 
-        println!("\ny = time[s], x = concurrent requests");
+        println!("\ny = {}[s], x = concurrent requests", output.metric);
         Chart::new(200, 120, min, max)
             .lineplot(&Shape::Lines(&points))
             .nice();
+
+        reports.push(BenchmarkReport::new(title, &config.tests, &results));
     }
+    // The blocking backend doesn't distinguish ordered/unordered completion, so
+    // running it a second time would just repeat the same pass under a
+    // different title; only the async backend gets a second, unordered run.
+    #[cfg(not(feature = "blocking"))]
     {
-        let results = benchmark::average_time(&config.tests, unordered_get, "Unrdered buffer - 250 requests").await;
+        let title = "Unrdered buffer - 250 requests";
+        let results = benchmark::average_time(&config.tests, &make_loader(false), title).await;
 
         let points: Vec<(f32,f32)> = config.tests.iter()
             .map(|t| t.concurrent_requests as f32)
             .zip(results.iter()
-                .map(|r| r.0))
+                .map(|r| metric_value(&r.stats, &output.metric)))
             .collect();
 
         let max = config.tests.iter().map(|t| t.concurrent_requests).max().unwrap() as f32;
         let min = config.tests.iter().map(|t| t.concurrent_requests).min().unwrap() as f32;
 
-        println!("\ny = time[s], x = concurrent requests");
+        println!("\ny = {}[s], x = concurrent requests", output.metric);
         Chart::new(200, 120, min, max)
             .lineplot(&Shape::Lines(&points))
             .nice();
+
+        reports.push(BenchmarkReport::new(title, &config.tests, &results));
     }
 
     let file = File::open("fixed_concurrent_requests_50.json")
@@ -123,39 +447,65 @@ async fn main() {
     let config: Config = serde_json::from_reader(file)
         .map_err(|e| e.into())
         .graceful_exit("The configuration file is incorrect.");
+    validate_config(&config);
 
     {
-        let results = benchmark::average_time(&config.tests, ordered_get, "Ordered buffer - 50 concurrent requests").await;
+        #[cfg(not(feature = "blocking"))]
+        let title = "Ordered buffer - 50 concurrent requests";
+        #[cfg(feature = "blocking")]
+        let title = "Blocking - 50 concurrent requests";
+        let results = benchmark::average_time(&config.tests, &make_loader(true), title).await;
 
         let points: Vec<(f32,f32)> = config.tests.iter()
             .map(|t| t.requests_number as f32)
             .zip(results.iter()
-                .map(|r| r.0))
+                .map(|r| metric_value(&r.stats, &output.metric)))
             .collect();
 
         let max = config.tests.iter().map(|t| t.requests_number).max().unwrap() as f32;
         let min = config.tests.iter().map(|t| t.requests_number).min().unwrap() as f32;
 
-        println!("\ny = time[s], x = requests number");
+        println!("\ny = {}[s], x = requests number", output.metric);
         Chart::new(200, 120, min, max)
             .lineplot(&Shape::Lines(&points))
             .nice();
+
+        reports.push(BenchmarkReport::new(title, &config.tests, &results));
     }
+    // See the matching comment above: the blocking backend has no ordered/
+    // unordered distinction, so it only runs once per config.
+    #[cfg(not(feature = "blocking"))]
     {
-        let results = benchmark::average_time(&config.tests, unordered_get, "Unrdered buffer - 50 concurrent requests").await;
+        let title = "Unrdered buffer - 50 concurrent requests";
+        let results = benchmark::average_time(&config.tests, &make_loader(false), title).await;
 
         let points: Vec<(f32,f32)> = config.tests.iter()
             .map(|t| t.requests_number as f32)
             .zip(results.iter()
-                .map(|r| r.0))
+                .map(|r| metric_value(&r.stats, &output.metric)))
             .collect();
 
         let max = config.tests.iter().map(|t| t.requests_number).max().unwrap() as f32;
         let min = config.tests.iter().map(|t| t.requests_number).min().unwrap() as f32;
 
-        println!("\ny = time[s], x = requests number");
+        println!("\ny = {}[s], x = requests number", output.metric);
         Chart::new(200, 120, min, max)
             .lineplot(&Shape::Lines(&points))
             .nice();
+
+        reports.push(BenchmarkReport::new(title, &config.tests, &results));
+    }
+
+    if let Some(path) = &output.json {
+        BenchmarkReport::write_json(path, &reports)
+            .map_err(|e| e.into())
+            .graceful_exit("Failed to write the JSON report.");
+        println!("\nWrote JSON report to {}", path);
+    }
+    if let Some(path) = &output.csv {
+        BenchmarkReport::write_csv(path, &reports)
+            .map_err(|e| e.into())
+            .graceful_exit("Failed to write the CSV report.");
+        println!("Wrote CSV report to {}", path);
     }
 }